@@ -0,0 +1,123 @@
+use std::convert::TryInto;
+use std::io;
+
+use crate::Result;
+
+use super::segment::JpegSegment;
+
+/// The maximum amount of profile/payload data that fits in a single
+/// ICC/EXIF chunk segment, leaving room for the prefix and sequence
+/// markers within the 16-bit segment length field.
+const MAX_CHUNK_LEN: usize = 65519;
+
+fn too_many_chunks() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "payload too large to split across 255 segments",
+    )
+}
+
+/// Split a full ICC color profile into the sequence of APP2 segments the
+/// ICC-within-JPEG convention uses: each chunk is tagged with a 1-based
+/// sequence number and the total chunk count, so a single profile larger
+/// than a single segment's ~64KB limit can still be written losslessly.
+///
+/// Returns an empty `Vec` if `profile` is empty.
+pub fn icc_segments(profile: &[u8]) -> Result<Vec<JpegSegment>> {
+    if profile.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let chunks: Vec<&[u8]> = profile.chunks(MAX_CHUNK_LEN).collect();
+    let num: u8 = chunks.len().try_into().map_err(|_| too_many_chunks())?;
+
+    Ok(chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| JpegSegment::new_icc((i + 1) as u8, num, chunk))
+        .collect())
+}
+
+/// Reassemble the full ICC color profile out of the (APP2) `segments` of a
+/// Jpeg image, undoing [`icc_segments`]. Non-ICC segments are ignored.
+///
+/// Returns `Ok(None)` if none of `segments` carry ICC data.
+pub fn reassemble_icc<'a>(
+    segments: impl IntoIterator<Item = &'a JpegSegment>,
+) -> Result<Option<Vec<u8>>> {
+    reassemble_chunks(segments.into_iter().filter_map(|s| s.icc()))
+}
+
+/// Split a full EXIF/TIFF buffer into the sequence of APP1 segments needed
+/// when it doesn't fit in a single ~64KB segment, using the same
+/// sequence-number/count convention as [`icc_segments`].
+///
+/// Returns an empty `Vec` if `buf` is empty. A `buf` that already fits in a
+/// single segment is still wrapped with an accompanying `seqno`/`num` of
+/// `1`; use [`super::segment::JpegSegment::new_exif`] directly if a plain,
+/// unnumbered single EXIF segment is required instead.
+pub fn exif_segments(buf: &[u8]) -> Result<Vec<JpegSegment>> {
+    if buf.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let chunks: Vec<&[u8]> = buf.chunks(MAX_CHUNK_LEN).collect();
+    let num: u8 = chunks.len().try_into().map_err(|_| too_many_chunks())?;
+
+    Ok(chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| JpegSegment::new_exif_chunk((i + 1) as u8, num, chunk))
+        .collect())
+}
+
+/// Reassemble a full EXIF/TIFF buffer out of the (APP1) `segments` of a
+/// Jpeg image, undoing [`exif_segments`]. Segments written by the plain,
+/// unnumbered [`super::segment::JpegSegment::new_exif`] are ignored; use
+/// [`super::segment::JpegSegment::exif`] for those.
+///
+/// Returns `Ok(None)` if none of `segments` carry chunked EXIF data.
+pub fn reassemble_exif<'a>(
+    segments: impl IntoIterator<Item = &'a JpegSegment>,
+) -> Result<Option<Vec<u8>>> {
+    reassemble_chunks(segments.into_iter().filter_map(|s| s.exif_chunk()))
+}
+
+/// Reassemble chunks carrying a `(seqno, num, data)` triple, as produced by
+/// both the ICC and the chunked-EXIF conventions, validating that every
+/// chunk from `1` to `num` is present exactly once.
+fn reassemble_chunks<'a>(
+    chunks: impl Iterator<Item = (u8, u8, &'a [u8])>,
+) -> Result<Option<Vec<u8>>> {
+    let mut chunks: Vec<(u8, u8, &[u8])> = chunks.collect();
+
+    if chunks.is_empty() {
+        return Ok(None);
+    }
+
+    chunks.sort_by_key(|&(seqno, _, _)| seqno);
+    let num = chunks[0].1;
+
+    if chunks.len() != num as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing chunk(s) while reassembling a split segment",
+        )
+        .into());
+    }
+
+    let mut buf = Vec::new();
+    for (i, (seqno, chunk_num, data)) in chunks.into_iter().enumerate() {
+        if chunk_num != num || seqno != (i + 1) as u8 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "inconsistent sequence numbers while reassembling a split segment",
+            )
+            .into());
+        }
+
+        buf.extend_from_slice(data);
+    }
+
+    Ok(Some(buf))
+}
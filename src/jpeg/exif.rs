@@ -0,0 +1,455 @@
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::io;
+
+use crate::Result;
+
+use super::segment::JpegSegment;
+
+/// The tag of the Exif sub-IFD pointer, as found in IFD0.
+pub const TAG_EXIF_IFD: u16 = 0x8769;
+/// The tag of the GPS sub-IFD pointer, as found in IFD0.
+pub const TAG_GPS_IFD: u16 = 0x8825;
+/// The tag of the Interoperability sub-IFD pointer, as found in the Exif IFD.
+pub const TAG_INTEROP_IFD: u16 = 0xa005;
+
+/// The byte order a TIFF/Exif block was encoded with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+impl Default for ByteOrder {
+    /// Defaults to little-endian, as emitted by most Exif writers.
+    fn default() -> ByteOrder {
+        ByteOrder::LittleEndian
+    }
+}
+
+impl ByteOrder {
+    fn read_u16(self, b: &[u8]) -> u16 {
+        match self {
+            ByteOrder::LittleEndian => u16::from_le_bytes(b.try_into().unwrap()),
+            ByteOrder::BigEndian => u16::from_be_bytes(b.try_into().unwrap()),
+        }
+    }
+
+    fn read_u32(self, b: &[u8]) -> u32 {
+        match self {
+            ByteOrder::LittleEndian => u32::from_le_bytes(b.try_into().unwrap()),
+            ByteOrder::BigEndian => u32::from_be_bytes(b.try_into().unwrap()),
+        }
+    }
+
+    fn read_i16(self, b: &[u8]) -> i16 {
+        self.read_u16(b) as i16
+    }
+
+    fn read_i32(self, b: &[u8]) -> i32 {
+        self.read_u32(b) as i32
+    }
+
+    fn read_f32(self, b: &[u8]) -> f32 {
+        f32::from_bits(self.read_u32(b))
+    }
+
+    fn read_f64(self, b: &[u8]) -> f64 {
+        match self {
+            ByteOrder::LittleEndian => f64::from_le_bytes(b.try_into().unwrap()),
+            ByteOrder::BigEndian => f64::from_be_bytes(b.try_into().unwrap()),
+        }
+    }
+
+    pub(super) fn write_u16(self, v: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::LittleEndian => v.to_le_bytes(),
+            ByteOrder::BigEndian => v.to_be_bytes(),
+        }
+    }
+
+    pub(super) fn write_u32(self, v: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::LittleEndian => v.to_le_bytes(),
+            ByteOrder::BigEndian => v.to_be_bytes(),
+        }
+    }
+
+    pub(super) fn write_i16(self, v: i16) -> [u8; 2] {
+        self.write_u16(v as u16)
+    }
+
+    pub(super) fn write_i32(self, v: i32) -> [u8; 4] {
+        self.write_u32(v as u32)
+    }
+
+    pub(super) fn write_f32(self, v: f32) -> [u8; 4] {
+        self.write_u32(v.to_bits())
+    }
+
+    pub(super) fn write_f64(self, v: f64) -> [u8; 8] {
+        match self {
+            ByteOrder::LittleEndian => v.to_le_bytes(),
+            ByteOrder::BigEndian => v.to_be_bytes(),
+        }
+    }
+}
+
+/// A decoded value of a single IFD entry.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    SByte(Vec<i8>),
+    Undefined(Vec<u8>),
+    SShort(Vec<i16>),
+    SLong(Vec<i32>),
+    SRational(Vec<(i32, i32)>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+}
+
+impl Value {
+    /// Interpret this value as a single unsigned 32-bit offset, as used by
+    /// sub-IFD pointer tags.
+    pub fn as_offset(&self) -> Option<u32> {
+        match self {
+            Value::Long(v) => v.first().copied(),
+            Value::Short(v) => v.first().map(|&v| v as u32),
+            _ => None,
+        }
+    }
+}
+
+fn type_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+fn decode_value(field_type: u16, count: u32, data: &[u8], byte_order: ByteOrder) -> Result<Value> {
+    let count = count as usize;
+
+    Ok(match field_type {
+        1 => Value::Byte(data.to_vec()),
+        2 => {
+            let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+            Value::Ascii(String::from_utf8_lossy(&data[..end]).into_owned())
+        }
+        3 => Value::Short(
+            (0..count)
+                .map(|i| byte_order.read_u16(&data[i * 2..i * 2 + 2]))
+                .collect(),
+        ),
+        4 => Value::Long(
+            (0..count)
+                .map(|i| byte_order.read_u32(&data[i * 4..i * 4 + 4]))
+                .collect(),
+        ),
+        5 => Value::Rational(
+            (0..count)
+                .map(|i| {
+                    let chunk = &data[i * 8..i * 8 + 8];
+                    (
+                        byte_order.read_u32(&chunk[0..4]),
+                        byte_order.read_u32(&chunk[4..8]),
+                    )
+                })
+                .collect(),
+        ),
+        6 => Value::SByte(data.iter().map(|&b| b as i8).collect()),
+        7 => Value::Undefined(data.to_vec()),
+        8 => Value::SShort(
+            (0..count)
+                .map(|i| byte_order.read_i16(&data[i * 2..i * 2 + 2]))
+                .collect(),
+        ),
+        9 => Value::SLong(
+            (0..count)
+                .map(|i| byte_order.read_i32(&data[i * 4..i * 4 + 4]))
+                .collect(),
+        ),
+        10 => Value::SRational(
+            (0..count)
+                .map(|i| {
+                    let chunk = &data[i * 8..i * 8 + 8];
+                    (
+                        byte_order.read_i32(&chunk[0..4]),
+                        byte_order.read_i32(&chunk[4..8]),
+                    )
+                })
+                .collect(),
+        ),
+        11 => Value::Float(
+            (0..count)
+                .map(|i| byte_order.read_f32(&data[i * 4..i * 4 + 4]))
+                .collect(),
+        ),
+        12 => Value::Double(
+            (0..count)
+                .map(|i| byte_order.read_f64(&data[i * 8..i * 8 + 8]))
+                .collect(),
+        ),
+        _ => return Err(invalid_data("unsupported Exif field type").into()),
+    })
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// A single decoded IFD entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub tag: u16,
+    pub value: Value,
+}
+
+/// A structured view over the TIFF/Exif payload found in a `JpegSegment`.
+///
+/// Produced by [`Exif::from_segment`] or [`Exif::from_bytes`], this decodes
+/// the chain of IFDs starting at IFD0 into typed [`Field`]s, and can resolve
+/// sub-IFD pointers (such as Exif and GPS) held by those IFDs on demand.
+/// Pointers held inside a sub-IFD, such as the Interop pointer of the Exif
+/// IFD, are not followed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exif {
+    byte_order: ByteOrder,
+    data: Vec<u8>,
+    ifds: Vec<Vec<Field>>,
+}
+
+impl Exif {
+    /// Parse the Exif payload (the bytes following the `Exif\0\0` prefix) of
+    /// `segment`, if it is one.
+    pub fn from_segment(segment: &JpegSegment) -> Result<Option<Exif>> {
+        match segment.exif() {
+            Some(data) => Ok(Some(Exif::from_bytes(data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Parse a raw TIFF block, as found right after the `Exif\0\0` prefix of
+    /// an APP1 segment.
+    pub fn from_bytes(data: &[u8]) -> Result<Exif> {
+        let bom = data
+            .get(0..2)
+            .ok_or_else(|| invalid_data("truncated TIFF header"))?;
+
+        let byte_order = match bom {
+            b"II" => ByteOrder::LittleEndian,
+            b"MM" => ByteOrder::BigEndian,
+            _ => return Err(invalid_data("unrecognized TIFF byte order mark").into()),
+        };
+
+        let magic = data
+            .get(2..4)
+            .map(|b| byte_order.read_u16(b))
+            .ok_or_else(|| invalid_data("truncated TIFF header"))?;
+
+        if magic != 42 {
+            return Err(invalid_data("invalid TIFF magic number").into());
+        }
+
+        let ifd0_offset = data
+            .get(4..8)
+            .map(|b| byte_order.read_u32(b))
+            .ok_or_else(|| invalid_data("truncated TIFF header"))?;
+
+        let mut ifds = Vec::new();
+        let mut visited = HashSet::new();
+        let mut offset = ifd0_offset;
+
+        while offset != 0 {
+            if !visited.insert(offset) {
+                return Err(invalid_data("cyclic IFD offset").into());
+            }
+
+            let (fields, next_offset) = read_ifd(data, offset, byte_order)?;
+            ifds.push(fields);
+            offset = next_offset;
+        }
+
+        Ok(Exif {
+            byte_order,
+            data: data.to_vec(),
+            ifds,
+        })
+    }
+
+    /// The byte order this block was encoded with.
+    pub fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    /// Iterate over every decoded field, as `(ifd_index, tag, value)`, where
+    /// `ifd_index` is the position of the owning IFD in the IFD0/IFD1/...
+    /// chain.
+    pub fn fields(&self) -> impl Iterator<Item = (usize, u16, &Value)> {
+        self.ifds
+            .iter()
+            .enumerate()
+            .flat_map(|(i, fields)| fields.iter().map(move |f| (i, f.tag, &f.value)))
+    }
+
+    /// The fields of the IFD at `ifd_index` in the main chain (IFD0 = `0`,
+    /// IFD1 = `1`, ...).
+    pub fn ifd(&self, ifd_index: usize) -> Option<&[Field]> {
+        self.ifds.get(ifd_index).map(|fields| fields.as_slice())
+    }
+
+    /// Resolve the sub-IFD pointed to by `tag` inside the IFD at
+    /// `ifd_index`, parsing it on demand.
+    pub fn sub_ifd(&self, ifd_index: usize, tag: u16) -> Result<Option<Vec<Field>>> {
+        let offset = match self
+            .ifds
+            .get(ifd_index)
+            .and_then(|fields| fields.iter().find(|f| f.tag == tag))
+            .and_then(|f| f.value.as_offset())
+        {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+
+        let (fields, _) = read_ifd(&self.data, offset, self.byte_order)?;
+        Ok(Some(fields))
+    }
+
+    /// Resolve the Exif sub-IFD (tag `0x8769`) referenced from IFD0.
+    pub fn exif_ifd(&self) -> Result<Option<Vec<Field>>> {
+        self.sub_ifd(0, TAG_EXIF_IFD)
+    }
+
+    /// Resolve the GPS sub-IFD (tag `0x8825`) referenced from IFD0.
+    pub fn gps_ifd(&self) -> Result<Option<Vec<Field>>> {
+        self.sub_ifd(0, TAG_GPS_IFD)
+    }
+}
+
+/// Read a single IFD at `offset` into the TIFF block `data`, returning its
+/// fields and the offset of the next IFD (`0` if there is none).
+fn read_ifd(data: &[u8], offset: u32, byte_order: ByteOrder) -> Result<(Vec<Field>, u32)> {
+    let offset = offset as usize;
+
+    let count = data
+        .get(offset..offset + 2)
+        .map(|b| byte_order.read_u16(b))
+        .ok_or_else(|| invalid_data("IFD entry count out of bounds"))? as usize;
+
+    let mut fields = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let entry_offset = offset + 2 + i * 12;
+        let entry = data
+            .get(entry_offset..entry_offset + 12)
+            .ok_or_else(|| invalid_data("IFD entry out of bounds"))?;
+
+        let tag = byte_order.read_u16(&entry[0..2]);
+        let field_type = byte_order.read_u16(&entry[2..4]);
+        let count = byte_order.read_u32(&entry[4..8]);
+
+        let elem_size = type_size(field_type)
+            .ok_or_else(|| invalid_data("unsupported Exif field type"))?;
+        let total_size = elem_size
+            .checked_mul(count as usize)
+            .ok_or_else(|| invalid_data("Exif field size overflow"))?;
+
+        let value_bytes = if total_size <= 4 {
+            &entry[8..8 + total_size]
+        } else {
+            let value_offset = byte_order.read_u32(&entry[8..12]) as usize;
+            data.get(value_offset..value_offset + total_size)
+                .ok_or_else(|| invalid_data("Exif field value out of bounds"))?
+        };
+
+        fields.push(Field {
+            tag,
+            value: decode_value(field_type, count, value_bytes, byte_order)?,
+        });
+    }
+
+    let next_ifd_offset = data
+        .get(offset + 2 + count * 12..offset + 2 + count * 12 + 4)
+        .map(|b| byte_order.read_u32(b))
+        .ok_or_else(|| invalid_data("next IFD offset out of bounds"))?;
+
+    Ok((fields, next_ifd_offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A minimal little-endian IFD0 with:
+    // - tag 0x0100 (ImageWidth), SHORT, count 2, inline (exercises a
+    //   multi-element inline field).
+    // - tag 0x829a (ExposureTime), RATIONAL, count 1, out-of-line (this is
+    //   the shape that used to panic: a single RATIONAL is 8 bytes, so the
+    //   read helpers were handed an 8-byte slice instead of the 4 bytes
+    //   they expect per number).
+    fn minimal_tiff() -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(b"II"); // byte order
+        buf.extend(42u16.to_le_bytes()); // magic
+        buf.extend(8u32.to_le_bytes()); // IFD0 offset
+
+        // IFD0: 2 entries.
+        buf.extend(2u16.to_le_bytes());
+
+        // Entry 0: ImageWidth, SHORT, count 2, inline (100, 200).
+        buf.extend(0x0100u16.to_le_bytes());
+        buf.extend(3u16.to_le_bytes());
+        buf.extend(2u32.to_le_bytes());
+        buf.extend(100u16.to_le_bytes());
+        buf.extend(200u16.to_le_bytes());
+
+        // Entry 1: ExposureTime, RATIONAL, count 1, out-of-line. The
+        // out-of-line RATIONAL data is appended after the next-IFD offset
+        // below, so its offset is computed once the rest of the IFD is
+        // known.
+        let rational_offset = buf.len() as u32 + 12 + 4;
+        buf.extend(0x829au16.to_le_bytes());
+        buf.extend(5u16.to_le_bytes());
+        buf.extend(1u32.to_le_bytes());
+        buf.extend(rational_offset.to_le_bytes());
+
+        // next IFD offset (none).
+        buf.extend(0u32.to_le_bytes());
+
+        assert_eq!(buf.len(), rational_offset as usize);
+        buf.extend(1u32.to_le_bytes());
+        buf.extend(30u32.to_le_bytes());
+
+        buf
+    }
+
+    #[test]
+    fn decodes_multi_element_and_rational_fields_without_panicking() {
+        let exif = Exif::from_bytes(&minimal_tiff()).unwrap();
+
+        let fields: Vec<_> = exif.fields().collect();
+        assert_eq!(fields.len(), 2);
+
+        assert_eq!(fields[0], (0, 0x0100, &Value::Short(vec![100, 200])));
+        assert_eq!(fields[1], (0, 0x829a, &Value::Rational(vec![(1, 30)])));
+    }
+
+    #[test]
+    fn rejects_truncated_header() {
+        assert!(Exif::from_bytes(b"II").is_err());
+    }
+
+    #[test]
+    fn rejects_unrecognized_byte_order() {
+        let mut buf = minimal_tiff();
+        buf[0] = b'X';
+        assert!(Exif::from_bytes(&buf).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use std::io;
+
+use crate::Result;
+
+use super::exif::{Field, Value};
+
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_JPEG_INTERCHANGE_FORMAT: u16 = 0x0201;
+const TAG_JPEG_INTERCHANGE_FORMAT_LENGTH: u16 = 0x0202;
+
+const COMPRESSION_UNCOMPRESSED: u32 = 1;
+const COMPRESSION_LZW: u32 = 5;
+const COMPRESSION_PACKBITS: u32 = 32773;
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn find_value(ifd: &[Field], tag: u16) -> Option<&Value> {
+    ifd.iter().find(|f| f.tag == tag).map(|f| &f.value)
+}
+
+fn as_u32_vec(value: &Value) -> Option<Vec<u32>> {
+    match value {
+        Value::Short(v) => Some(v.iter().map(|&x| u32::from(x)).collect()),
+        Value::Long(v) => Some(v.clone()),
+        _ => None,
+    }
+}
+
+fn as_u32(value: &Value) -> Option<u32> {
+    as_u32_vec(value).and_then(|v| v.into_iter().next())
+}
+
+/// Decode the embedded thumbnail of an Exif IFD1, be it a baseline JPEG
+/// (tags `0x0201`/`0x0202`) or raw strips under a TIFF compression scheme
+/// (uncompressed, PackBits or LZW), resolved against `tiff_data` (the full
+/// TIFF block IFD1 was parsed from).
+pub fn decode_thumbnail(ifd1: &[Field], tiff_data: &[u8]) -> Result<Vec<u8>> {
+    let jpeg_offset = find_value(ifd1, TAG_JPEG_INTERCHANGE_FORMAT).and_then(as_u32);
+    let jpeg_length = find_value(ifd1, TAG_JPEG_INTERCHANGE_FORMAT_LENGTH).and_then(as_u32);
+
+    if let (Some(offset), Some(len)) = (jpeg_offset, jpeg_length) {
+        return read_slice(tiff_data, offset, len).map(<[u8]>::to_vec);
+    }
+
+    let strip_offsets = find_value(ifd1, TAG_STRIP_OFFSETS)
+        .and_then(as_u32_vec)
+        .ok_or_else(|| invalid_data("IFD1 has no thumbnail strip offsets"))?;
+    let strip_byte_counts = find_value(ifd1, TAG_STRIP_BYTE_COUNTS)
+        .and_then(as_u32_vec)
+        .ok_or_else(|| invalid_data("IFD1 has no thumbnail strip byte counts"))?;
+
+    if strip_offsets.len() != strip_byte_counts.len() {
+        return Err(invalid_data("thumbnail strip offsets/byte counts count mismatch").into());
+    }
+
+    let compression = find_value(ifd1, TAG_COMPRESSION)
+        .and_then(as_u32)
+        .unwrap_or(COMPRESSION_UNCOMPRESSED);
+
+    let mut out = Vec::new();
+    for (&offset, &len) in strip_offsets.iter().zip(&strip_byte_counts) {
+        let strip = read_slice(tiff_data, offset, len)?;
+
+        match compression {
+            COMPRESSION_UNCOMPRESSED => out.extend_from_slice(strip),
+            COMPRESSION_PACKBITS => decode_packbits(strip, &mut out)?,
+            COMPRESSION_LZW => decode_lzw(strip, &mut out)?,
+            _ => return Err(invalid_data("unsupported thumbnail compression scheme").into()),
+        }
+    }
+
+    Ok(out)
+}
+
+fn read_slice(data: &[u8], offset: u32, len: u32) -> Result<&[u8]> {
+    let start = offset as usize;
+    let end = start
+        .checked_add(len as usize)
+        .ok_or_else(|| invalid_data("thumbnail strip size overflow"))?;
+
+    data.get(start..end)
+        .ok_or_else(|| invalid_data("thumbnail strip out of bounds").into())
+}
+
+/// Decode a PackBits-compressed strip, appending the result to `out`.
+fn decode_packbits(data: &[u8], out: &mut Vec<u8>) -> Result<()> {
+    let mut i = 0;
+
+    while i < data.len() {
+        let n = data[i] as i8;
+        i += 1;
+
+        if n >= 0 {
+            let count = n as usize + 1;
+            let literal = data
+                .get(i..i + count)
+                .ok_or_else(|| invalid_data("PackBits literal run out of bounds"))?;
+            out.extend_from_slice(literal);
+            i += count;
+        } else if n != -128 {
+            let count = 1 - n as i32;
+            let byte = *data
+                .get(i)
+                .ok_or_else(|| invalid_data("PackBits repeat run out of bounds"))?;
+            out.resize(out.len() + count as usize, byte);
+            i += 1;
+        }
+        // n == -128 is a no-op, used for padding.
+    }
+
+    Ok(())
+}
+
+const LZW_CLEAR_CODE: u16 = 256;
+const LZW_EOI_CODE: u16 = 257;
+
+/// Reads big-endian, MSB-first variable-width bit codes, as used by TIFF
+/// LZW streams.
+struct BitReader<'a> {
+    data: &'a [u8],
+    byte_pos: usize,
+    bit_pos: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data,
+            byte_pos: 0,
+            bit_pos: 0,
+        }
+    }
+
+    fn read_code(&mut self, width: u32) -> Option<u16> {
+        let mut value = 0u16;
+
+        for _ in 0..width {
+            let byte = *self.data.get(self.byte_pos)?;
+            let bit = (byte >> (7 - self.bit_pos)) & 1;
+            value = (value << 1) | u16::from(bit);
+
+            self.bit_pos += 1;
+            if self.bit_pos == 8 {
+                self.bit_pos = 0;
+                self.byte_pos += 1;
+            }
+        }
+
+        Some(value)
+    }
+}
+
+/// Decode a TIFF-style LZW-compressed strip, appending the result to `out`.
+///
+/// Codes start at 9 bits wide; code 256 (`ClearCode`) resets the dictionary
+/// and 257 (`EoiCode`) ends the stream. Following the TIFF6 specification
+/// (unlike the GIF variant of LZW), the code width grows one code early,
+/// i.e. as soon as the table holds `2^width - 1` entries rather than once it
+/// is full.
+fn decode_lzw(data: &[u8], out: &mut Vec<u8>) -> Result<()> {
+    let mut reader = BitReader::new(data);
+    let mut table: Vec<Vec<u8>> = Vec::new();
+    let mut code_width = 9u32;
+    let mut prev: Option<Vec<u8>> = None;
+
+    reset_table(&mut table);
+
+    loop {
+        let code = reader
+            .read_code(code_width)
+            .ok_or_else(|| invalid_data("truncated LZW stream"))?;
+
+        if code == LZW_CLEAR_CODE {
+            reset_table(&mut table);
+            code_width = 9;
+            prev = None;
+            continue;
+        }
+
+        if code == LZW_EOI_CODE {
+            break;
+        }
+
+        let entry = if (code as usize) < table.len() {
+            table[code as usize].clone()
+        } else if code as usize == table.len() {
+            let mut entry = prev
+                .clone()
+                .ok_or_else(|| invalid_data("LZW code referenced before any data"))?;
+            let first = entry[0];
+            entry.push(first);
+            entry
+        } else {
+            return Err(invalid_data("LZW code out of range").into());
+        };
+
+        out.extend_from_slice(&entry);
+
+        if let Some(prev_entry) = prev {
+            let mut new_entry = prev_entry;
+            new_entry.push(entry[0]);
+            table.push(new_entry);
+
+            if matches!(table.len(), 511 | 1023 | 2047) {
+                code_width += 1;
+            }
+        }
+
+        prev = Some(entry);
+    }
+
+    Ok(())
+}
+
+fn reset_table(table: &mut Vec<Vec<u8>>) {
+    table.clear();
+    for b in 0u16..256 {
+        table.push(vec![b as u8]);
+    }
+    // Indices 256 (ClearCode) and 257 (EoiCode) are never looked up as
+    // dictionary entries, but are reserved so the first new entry starts
+    // at 258, matching the TIFF LZW table layout.
+    table.push(Vec::new());
+    table.push(Vec::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_packbits_literal_and_repeat_runs() {
+        // Literal run of 3 bytes, then a repeat of 4 copies of 0xAA, then a
+        // no-op padding byte (-128).
+        let data = [2, 1, 2, 3, (-3i8) as u8, 0xAA, 0x80];
+
+        let mut out = Vec::new();
+        decode_packbits(&data, &mut out).unwrap();
+
+        assert_eq!(out, vec![1, 2, 3, 0xAA, 0xAA, 0xAA, 0xAA]);
+    }
+
+    #[test]
+    fn packbits_literal_run_out_of_bounds_errors() {
+        let data = [2, 1, 2]; // claims 3 literal bytes, only 2 given
+        let mut out = Vec::new();
+        assert!(decode_packbits(&data, &mut out).is_err());
+    }
+
+    // Pack `(code, width)` pairs MSB-first into bytes, as read by
+    // `BitReader`, zero-padding the last byte.
+    fn encode_bits(codes: &[(u16, u32)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut cur = 0u32;
+        let mut bits = 0u32;
+
+        for &(code, width) in codes {
+            cur = (cur << width) | u32::from(code);
+            bits += width;
+
+            while bits >= 8 {
+                let shift = bits - 8;
+                bytes.push(((cur >> shift) & 0xff) as u8);
+                bits -= 8;
+            }
+        }
+
+        if bits > 0 {
+            bytes.push(((cur << (8 - bits)) & 0xff) as u8);
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn decodes_lzw_literal_codes_and_eoi() {
+        // ClearCode, literal 'A' (65), literal 'B' (66), EoiCode, all as
+        // 9-bit codes.
+        let data = encode_bits(&[
+            (LZW_CLEAR_CODE, 9),
+            (65, 9),
+            (66, 9),
+            (LZW_EOI_CODE, 9),
+        ]);
+
+        let mut out = Vec::new();
+        decode_lzw(&data, &mut out).unwrap();
+
+        assert_eq!(out, vec![b'A', b'B']);
+    }
+
+    #[test]
+    fn decode_lzw_reads_new_table_entry_with_9_bit_codes() {
+        // ClearCode, then two literals to seed a new table entry (258:
+        // "AB"), then code 258 itself, then EoiCode, all as 9-bit codes:
+        // the table never holds more than 260 entries, well short of the
+        // 511 at which codes widen to 10 bits.
+        let data = encode_bits(&[
+            (LZW_CLEAR_CODE, 9),
+            (b'A' as u16, 9),
+            (b'B' as u16, 9),
+            (258, 9),
+            (LZW_EOI_CODE, 9),
+        ]);
+
+        let mut out = Vec::new();
+        decode_lzw(&data, &mut out).unwrap();
+
+        assert_eq!(out, vec![b'A', b'B', b'A', b'B']);
+    }
+}
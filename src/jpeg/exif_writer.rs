@@ -0,0 +1,373 @@
+use super::exif::{ByteOrder, Field, Value, TAG_EXIF_IFD, TAG_GPS_IFD, TAG_INTEROP_IFD};
+use super::segment::JpegSegment;
+
+/// Builds a TIFF/Exif block and wraps it into an APP1 `JpegSegment`.
+///
+/// Fields are grouped the way TIFF groups them: a primary IFD (IFD0), an
+/// Exif sub-IFD, a GPS sub-IFD and an Interoperability sub-IFD, plus an
+/// optional second IFD (IFD1) describing an embedded thumbnail. Pointers
+/// between IFDs (and to any value that doesn't fit inline) are computed and
+/// back-patched when the block is serialized with [`ExifBuilder::build`].
+#[derive(Clone, Debug, Default)]
+pub struct ExifBuilder {
+    byte_order: ByteOrder,
+    ifd0: Vec<Field>,
+    exif: Vec<Field>,
+    gps: Vec<Field>,
+    interop: Vec<Field>,
+    ifd1: Option<Vec<Field>>,
+}
+
+impl ExifBuilder {
+    /// Construct an empty `ExifBuilder` that will encode its TIFF block in
+    /// `byte_order`.
+    pub fn new(byte_order: ByteOrder) -> ExifBuilder {
+        ExifBuilder {
+            byte_order,
+            ifd0: Vec::new(),
+            exif: Vec::new(),
+            gps: Vec::new(),
+            interop: Vec::new(),
+            ifd1: None,
+        }
+    }
+
+    /// Add a field to IFD0, the primary IFD.
+    pub fn push_ifd0(&mut self, tag: u16, value: Value) -> &mut Self {
+        self.ifd0.push(Field { tag, value });
+        self
+    }
+
+    /// Add a field to the Exif sub-IFD.
+    pub fn push_exif(&mut self, tag: u16, value: Value) -> &mut Self {
+        self.exif.push(Field { tag, value });
+        self
+    }
+
+    /// Add a field to the GPS sub-IFD.
+    pub fn push_gps(&mut self, tag: u16, value: Value) -> &mut Self {
+        self.gps.push(Field { tag, value });
+        self
+    }
+
+    /// Add a field to the Interoperability sub-IFD.
+    pub fn push_interop(&mut self, tag: u16, value: Value) -> &mut Self {
+        self.interop.push(Field { tag, value });
+        self
+    }
+
+    /// Set the fields of IFD1, chained off IFD0's next-IFD pointer. This is
+    /// typically used to describe an embedded thumbnail (tags
+    /// `0x0201`/`0x0202` for a JPEG thumbnail, for instance).
+    pub fn set_ifd1(&mut self, fields: Vec<Field>) -> &mut Self {
+        self.ifd1 = Some(fields);
+        self
+    }
+
+    /// Serialize this builder into a standalone TIFF block, as found right
+    /// after the `Exif\0\0` prefix of an APP1 segment.
+    pub fn build(&self) -> Vec<u8> {
+        let has_exif_ifd = !self.exif.is_empty() || !self.interop.is_empty();
+        let has_gps_ifd = !self.gps.is_empty();
+        let has_interop_ifd = !self.interop.is_empty();
+
+        let mut ifd0 = self.ifd0.clone();
+        if has_exif_ifd {
+            ifd0.push(Field {
+                tag: TAG_EXIF_IFD,
+                value: Value::Long(vec![0]),
+            });
+        }
+        if has_gps_ifd {
+            ifd0.push(Field {
+                tag: TAG_GPS_IFD,
+                value: Value::Long(vec![0]),
+            });
+        }
+
+        let mut exif = self.exif.clone();
+        if has_interop_ifd {
+            exif.push(Field {
+                tag: TAG_INTEROP_IFD,
+                value: Value::Long(vec![0]),
+            });
+        }
+
+        // Each block is laid out in turn right after the 8-byte TIFF
+        // header: IFD0, then the (optional) Exif/GPS/Interop sub-IFDs, then
+        // the (optional) IFD1 thumbnail, then the pool of out-of-line
+        // values that didn't fit in their 4-byte entry slot.
+        let mut blocks = vec![ifd0];
+        let exif_block = has_exif_ifd.then_some(blocks.len());
+        if has_exif_ifd {
+            blocks.push(exif);
+        }
+        let gps_block = has_gps_ifd.then_some(blocks.len());
+        if has_gps_ifd {
+            blocks.push(self.gps.clone());
+        }
+        let interop_block = has_interop_ifd.then_some(blocks.len());
+        if has_interop_ifd {
+            blocks.push(self.interop.clone());
+        }
+        let ifd1_block = self.ifd1.as_ref().map(|_| blocks.len());
+        if let Some(ifd1) = &self.ifd1 {
+            blocks.push(ifd1.clone());
+        }
+
+        // TIFF requires each IFD's entries to be sorted in ascending order
+        // by tag.
+        for block in &mut blocks {
+            block.sort_by_key(|field| field.tag);
+        }
+
+        let block_size = |fields: &[Field]| -> usize { 2 + 12 * fields.len() + 4 };
+
+        let mut block_offsets = Vec::with_capacity(blocks.len());
+        let mut offset = 8usize;
+        for block in &blocks {
+            block_offsets.push(offset);
+            offset += block_size(block);
+        }
+        let pool_start = offset;
+
+        // Patch the sub-IFD pointer fields now that every block's offset is
+        // known.
+        if let Some(i) = exif_block {
+            patch_pointer(&mut blocks[0], TAG_EXIF_IFD, block_offsets[i] as u32);
+        }
+        if let Some(i) = gps_block {
+            patch_pointer(&mut blocks[0], TAG_GPS_IFD, block_offsets[i] as u32);
+        }
+        if let Some(i) = interop_block {
+            let exif_i = exif_block.expect("Interop IFD implies an Exif IFD");
+            patch_pointer(&mut blocks[exif_i], TAG_INTEROP_IFD, block_offsets[i] as u32);
+        }
+
+        let mut out = Vec::new();
+        out.extend(match self.byte_order {
+            ByteOrder::LittleEndian => *b"II",
+            ByteOrder::BigEndian => *b"MM",
+        });
+        out.extend(self.byte_order.write_u16(42));
+        out.extend(self.byte_order.write_u32(block_offsets[0] as u32));
+
+        let mut pool = Vec::new();
+        let mut pool_offset = pool_start;
+
+        for (i, fields) in blocks.iter().enumerate() {
+            out.extend(self.byte_order.write_u16(fields.len() as u16));
+
+            for field in fields {
+                let (type_code, count, value_bytes) = encode_value(&field.value, self.byte_order);
+
+                out.extend(self.byte_order.write_u16(field.tag));
+                out.extend(self.byte_order.write_u16(type_code));
+                out.extend(self.byte_order.write_u32(count));
+
+                if value_bytes.len() <= 4 {
+                    let mut inline = value_bytes;
+                    inline.resize(4, 0);
+                    out.extend(inline);
+                } else {
+                    out.extend(self.byte_order.write_u32(pool_offset as u32));
+                    pool_offset += value_bytes.len();
+                    pool.extend(value_bytes);
+
+                    // Out-of-line values must start on a word (even)
+                    // boundary. `is_multiple_of` is too new to rely on here.
+                    #[allow(clippy::manual_is_multiple_of)]
+                    if pool_offset % 2 != 0 {
+                        pool.push(0);
+                        pool_offset += 1;
+                    }
+                }
+            }
+
+            // IFD0 chains to IFD1 if one was provided; every other IFD in
+            // this block (including IFD1 itself) terminates the chain.
+            let next_ifd_offset = if i == 0 {
+                ifd1_block.map(|i| block_offsets[i] as u32).unwrap_or(0)
+            } else {
+                0
+            };
+            out.extend(self.byte_order.write_u32(next_ifd_offset));
+        }
+
+        out.extend(pool);
+        out
+    }
+
+    /// Serialize this builder and wrap the result in a new APP1
+    /// `JpegSegment`.
+    pub fn build_segment(&self) -> JpegSegment {
+        JpegSegment::new_exif(&self.build())
+    }
+}
+
+/// Overwrite the (inline, `LONG`) value of the entry tagged `tag` in
+/// `fields` with `offset`.
+fn patch_pointer(fields: &mut [Field], tag: u16, offset: u32) {
+    if let Some(field) = fields.iter_mut().find(|f| f.tag == tag) {
+        field.value = Value::Long(vec![offset]);
+    }
+}
+
+/// Encode `value`'s type code, element count and raw bytes, ready to be
+/// inlined in an entry's value slot or appended to the out-of-line pool.
+fn encode_value(value: &Value, byte_order: ByteOrder) -> (u16, u32, Vec<u8>) {
+    match value {
+        Value::Byte(v) => (1, v.len() as u32, v.clone()),
+        Value::Ascii(s) => {
+            let mut bytes = s.as_bytes().to_vec();
+            bytes.push(0);
+            let count = bytes.len() as u32;
+            (2, count, bytes)
+        }
+        Value::Short(v) => (
+            3,
+            v.len() as u32,
+            v.iter().flat_map(|&x| byte_order.write_u16(x)).collect(),
+        ),
+        Value::Long(v) => (
+            4,
+            v.len() as u32,
+            v.iter().flat_map(|&x| byte_order.write_u32(x)).collect(),
+        ),
+        Value::Rational(v) => {
+            let mut bytes = Vec::with_capacity(v.len() * 8);
+            for &(n, d) in v {
+                bytes.extend(byte_order.write_u32(n));
+                bytes.extend(byte_order.write_u32(d));
+            }
+            (5, v.len() as u32, bytes)
+        }
+        Value::SByte(v) => (6, v.len() as u32, v.iter().map(|&x| x as u8).collect()),
+        Value::Undefined(v) => (7, v.len() as u32, v.clone()),
+        Value::SShort(v) => (
+            8,
+            v.len() as u32,
+            v.iter().flat_map(|&x| byte_order.write_i16(x)).collect(),
+        ),
+        Value::SLong(v) => (
+            9,
+            v.len() as u32,
+            v.iter().flat_map(|&x| byte_order.write_i32(x)).collect(),
+        ),
+        Value::SRational(v) => {
+            let mut bytes = Vec::with_capacity(v.len() * 8);
+            for &(n, d) in v {
+                bytes.extend(byte_order.write_i32(n));
+                bytes.extend(byte_order.write_i32(d));
+            }
+            (10, v.len() as u32, bytes)
+        }
+        Value::Float(v) => (
+            11,
+            v.len() as u32,
+            v.iter().flat_map(|&x| byte_order.write_f32(x)).collect(),
+        ),
+        Value::Double(v) => (
+            12,
+            v.len() as u32,
+            v.iter().flat_map(|&x| byte_order.write_f64(x)).collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::exif::Exif;
+    use super::*;
+
+    #[test]
+    fn round_trips_through_exif_reader() {
+        let mut builder = ExifBuilder::new(ByteOrder::LittleEndian);
+        builder
+            .push_ifd0(0x0100, Value::Short(vec![100, 200]))
+            .push_ifd0(0x0110, Value::Ascii("Camera".to_string()))
+            .push_exif(0x829a, Value::Rational(vec![(1, 30)]))
+            .push_gps(0x0001, Value::Ascii("N".to_string()));
+
+        let exif = Exif::from_bytes(&builder.build()).unwrap();
+
+        assert_eq!(
+            exif.ifd(0).unwrap()[0],
+            Field {
+                tag: 0x0100,
+                value: Value::Short(vec![100, 200]),
+            }
+        );
+        assert_eq!(
+            exif.ifd(0).unwrap()[1],
+            Field {
+                tag: 0x0110,
+                value: Value::Ascii("Camera".to_string()),
+            }
+        );
+
+        let exif_ifd = exif.exif_ifd().unwrap().unwrap();
+        assert_eq!(
+            exif_ifd,
+            vec![Field {
+                tag: 0x829a,
+                value: Value::Rational(vec![(1, 30)]),
+            }]
+        );
+
+        let gps_ifd = exif.gps_ifd().unwrap().unwrap();
+        assert_eq!(
+            gps_ifd,
+            vec![Field {
+                tag: 0x0001,
+                value: Value::Ascii("N".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn sorts_ifd_entries_by_tag_regardless_of_push_order() {
+        let mut builder = ExifBuilder::new(ByteOrder::LittleEndian);
+        builder
+            .push_ifd0(0x0110, Value::Ascii("Camera".to_string()))
+            .push_ifd0(0x0100, Value::Short(vec![100, 200]));
+
+        let exif = Exif::from_bytes(&builder.build()).unwrap();
+        let tags: Vec<u16> = exif.ifd(0).unwrap().iter().map(|f| f.tag).collect();
+
+        assert_eq!(tags, vec![0x0100, 0x0110]);
+    }
+
+    #[test]
+    fn pads_odd_length_pool_values_to_an_even_offset() {
+        let mut builder = ExifBuilder::new(ByteOrder::LittleEndian);
+        // An odd-length out-of-line ASCII value ("ABCD\0" is 5 bytes, too
+        // long to inline) forces the next out-of-line value to fall on an
+        // odd offset unless it's padded.
+        builder
+            .push_ifd0(0x0001, Value::Ascii("ABCD".to_string()))
+            .push_ifd0(0x0002, Value::Rational(vec![(1, 2)]));
+
+        let tiff = builder.build();
+
+        // The pool starts at 8 + (2 + 2 * 12 + 4) = 38; "ABCD\0" ends at 43,
+        // so the RATIONAL (whose offset is in entry 1's value slot, bytes
+        // 30..34) starts at the padded offset 44.
+        assert_eq!(u32::from_le_bytes([tiff[30], tiff[31], tiff[32], tiff[33]]), 44);
+
+        let exif = Exif::from_bytes(&tiff).unwrap();
+        assert_eq!(
+            exif.ifd(0).unwrap(),
+            &[
+                Field {
+                    tag: 0x0001,
+                    value: Value::Ascii("ABCD".to_string()),
+                },
+                Field {
+                    tag: 0x0002,
+                    value: Value::Rational(vec![(1, 2)]),
+                },
+            ]
+        );
+    }
+}
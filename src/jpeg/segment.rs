@@ -10,6 +10,14 @@ use crate::{Result, EXIF_DATA_PREFIX};
 
 const ICC_DATA_PREFIX: &[u8] = b"ICC_PROFILE\0";
 
+/// Prefix of a chunk of a multi-segment EXIF block, as written by
+/// [`JpegSegment::new_exif_chunk`]. Distinct from [`EXIF_DATA_PREFIX`] (used
+/// by [`JpegSegment::new_exif`]) so a plain, unnumbered EXIF segment and a
+/// chunk of a split EXIF block can never be mistaken for one another: a
+/// plain segment's payload is TIFF data starting with a "II"/"MM" byte order
+/// mark, which can't collide with this prefix.
+const EXIF_CHUNK_DATA_PREFIX: &[u8] = b"ExifChunk\0";
+
 /// The representation of a single segment composing a Jpeg image.
 #[derive(Clone, PartialEq)]
 pub struct JpegSegment {
@@ -70,6 +78,20 @@ impl JpegSegment {
         JpegSegment::new_with_contents(markers::APP1, contents)
     }
 
+    /// Creates an EXIF `JpegSegment` that is one chunk of a larger EXIF
+    /// block split across several segments, using the same sequence
+    /// number/count convention as [`JpegSegment::new_icc`], under a prefix
+    /// distinct from a plain [`JpegSegment::new_exif`] segment.
+    pub(super) fn new_exif_chunk(seqno: u8, num: u8, buf: &[u8]) -> JpegSegment {
+        let mut contents = Vec::with_capacity(EXIF_CHUNK_DATA_PREFIX.len() + 2 + buf.len());
+        contents.extend(EXIF_CHUNK_DATA_PREFIX);
+        contents.push(seqno);
+        contents.push(num);
+        contents.extend(buf);
+
+        JpegSegment::new_with_contents(markers::APP1, contents)
+    }
+
     /// Create a `JpegSegment` with a length from a Reader.
     pub fn read(marker: u8, r: &mut dyn Read) -> Result<JpegSegment> {
         let size = r.read_u16::<BigEndian>()? - 2;
@@ -168,11 +190,38 @@ impl JpegSegment {
         }
     }
 
+    /// Returns the sequence number, count and payload of this `JpegSegment`
+    /// if it is one chunk of a multi-segment EXIF block created by
+    /// [`JpegSegment::new_exif_chunk`].
+    pub(super) fn exif_chunk(&self) -> Option<(u8, u8, &[u8])> {
+        if self.marker == markers::APP1
+            && self.contents.get(..EXIF_CHUNK_DATA_PREFIX.len()) == Some(EXIF_CHUNK_DATA_PREFIX)
+        {
+            let seqno = *self.contents.get(EXIF_CHUNK_DATA_PREFIX.len())?;
+            let num = *self.contents.get(EXIF_CHUNK_DATA_PREFIX.len() + 1)?;
+            let buf = self.contents.get(EXIF_CHUNK_DATA_PREFIX.len() + 2..)?;
+
+            Some((seqno, num, buf))
+        } else {
+            None
+        }
+    }
+
     /// Encode this `JpegSegment` and write it to a Writer.
-    pub fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+    ///
+    /// # Errors
+    ///
+    /// This method fails if writing fails, or if this segment's encoded
+    /// length (excluding the marker) doesn't fit in the 16-bit length field,
+    /// i.e. its content is larger than 65533 bytes.
+    pub fn write_to(&self, w: &mut dyn Write) -> Result<()> {
+        let len: u16 = (self.len() - 2)
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "JpegSegment content too large to encode in a single segment (max 65533 bytes)"))?;
+
         w.write_u8(markers::P)?;
         w.write_u8(self.marker())?;
-        w.write_u16::<BigEndian>((self.len() - 2).try_into().unwrap())?;
+        w.write_u16::<BigEndian>(len)?;
         w.write_all(&self.contents)?;
 
         if let Some(entropy) = &self.entropy {
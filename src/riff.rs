@@ -206,14 +206,14 @@ impl fmt::Debug for RiffChunk {
     }
 }
 
-fn has_subchunks(id: [u8; 4]) -> bool {
+pub(crate) fn has_subchunks(id: [u8; 4]) -> bool {
     match &id {
         b"RIFF" | b"LIST" | b"seqt" => true,
         _ => false,
     }
 }
 
-fn has_kind(id: [u8; 4]) -> bool {
+pub(crate) fn has_kind(id: [u8; 4]) -> bool {
     match &id {
         b"RIFF" | b"LIST" => true,
         _ => false,
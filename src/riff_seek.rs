@@ -0,0 +1,147 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::riff::{has_kind, has_subchunks};
+use crate::{Error, Result};
+
+/// A lazily-read node of a RIFF chunk tree.
+///
+/// Unlike [`crate::riff::RiffChunk`], which eagerly copies every chunk's
+/// payload into memory, a `RiffChunkDescriptor` only records a chunk's `id`
+/// and the offset/length of its payload within the underlying reader.
+/// `List`-like chunks (`RIFF`, `LIST`, `seqt`) are still walked recursively
+/// so their subchunks can be inspected, but `Data` payloads are skipped
+/// over with `Seek` rather than read. Call [`RiffChunkDescriptor::read_data`]
+/// to stream a specific chunk's bytes on demand.
+#[allow(clippy::len_without_is_empty)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RiffChunkDescriptor {
+    id: [u8; 4],
+    offset: u64,
+    len: u64,
+    kind: Option<[u8; 4]>,
+    subchunks: Vec<RiffChunkDescriptor>,
+}
+
+impl RiffChunkDescriptor {
+    /// Parse the chunk tree of a RIFF file from `r`, starting at its
+    /// current position.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if reading/seeking fails, or if the first chunk
+    /// doesn't have an id of "RIFF".
+    pub fn from_reader(r: &mut (impl Read + Seek)) -> Result<RiffChunkDescriptor> {
+        RiffChunkDescriptor::read(r, true)
+    }
+
+    fn read(r: &mut (impl Read + Seek), check_riff_id: bool) -> Result<RiffChunkDescriptor> {
+        let mut id = [0u8; 4];
+        r.read_exact(&mut id)?;
+
+        if check_riff_id && id != *b"RIFF" {
+            return Err(Error::NoRiffHeader);
+        }
+
+        let mut len_buf = [0u8; 4];
+        r.read_exact(&mut len_buf)?;
+        let len = u64::from(u32::from_le_bytes(len_buf));
+
+        let offset = r.stream_position()?;
+
+        if has_subchunks(id) {
+            let kind = if has_kind(id) {
+                let mut buf = [0u8; 4];
+                r.read_exact(&mut buf)?;
+                Some(buf)
+            } else {
+                None
+            };
+
+            let mut consumed = if kind.is_some() { 4 } else { 0 };
+            let mut subchunks = Vec::new();
+            while consumed < len {
+                let subchunk = RiffChunkDescriptor::read(r, false)?;
+                // 4 bytes id + 4 bytes length + payload + the pad byte, if any.
+                consumed += 8 + subchunk.len + (subchunk.len % 2);
+                subchunks.push(subchunk);
+            }
+
+            Ok(RiffChunkDescriptor {
+                id,
+                offset,
+                len,
+                kind,
+                subchunks,
+            })
+        } else {
+            // Skip the payload (and the pad byte, if the size is odd)
+            // without reading it into memory.
+            let pad = len % 2;
+            r.seek(SeekFrom::Current((len + pad) as i64))?;
+
+            Ok(RiffChunkDescriptor {
+                id,
+                offset,
+                len,
+                kind: None,
+                subchunks: Vec::new(),
+            })
+        }
+    }
+
+    /// Get the id of this chunk.
+    #[inline]
+    pub fn id(&self) -> [u8; 4] {
+        self.id
+    }
+
+    /// Get the byte offset of this chunk's payload within the reader it was
+    /// parsed from.
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get the length of this chunk's payload, excluding the pad byte.
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Get the `kind` of this chunk (e.g. `WEBP` for a `RIFF` chunk), if it
+    /// has one.
+    #[inline]
+    pub fn kind(&self) -> Option<[u8; 4]> {
+        self.kind
+    }
+
+    /// Get the subchunks of this chunk, if it is a `RIFF`/`LIST`/`seqt`
+    /// list. Empty for a `Data` chunk.
+    #[inline]
+    pub fn subchunks(&self) -> &[RiffChunkDescriptor] {
+        &self.subchunks
+    }
+
+    /// Find the first direct or nested subchunk with id `id`, depth-first.
+    pub fn find(&self, id: [u8; 4]) -> Option<&RiffChunkDescriptor> {
+        self.subchunks.iter().find_map(|chunk| {
+            if chunk.id == id {
+                Some(chunk)
+            } else {
+                chunk.find(id)
+            }
+        })
+    }
+
+    /// Read this chunk's payload from `r` on demand.
+    ///
+    /// `r` must be the same (or an equivalent) reader this descriptor was
+    /// parsed from.
+    pub fn read_data(&self, r: &mut (impl Read + Seek)) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; self.len as usize];
+        r.seek(SeekFrom::Start(self.offset))?;
+        r.read_exact(&mut buf)?;
+
+        Ok(buf)
+    }
+}
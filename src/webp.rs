@@ -0,0 +1,335 @@
+use std::convert::TryInto;
+use std::io;
+
+use bytes::Bytes;
+
+use crate::riff::{RiffChunk, RiffContent};
+use crate::Result;
+
+const VP8X_ID: [u8; 4] = *b"VP8X";
+const ANIM_ID: [u8; 4] = *b"ANIM";
+const ANMF_ID: [u8; 4] = *b"ANMF";
+const ALPH_ID: [u8; 4] = *b"ALPH";
+const VP8_ID: [u8; 4] = *b"VP8 ";
+const VP8L_ID: [u8; 4] = *b"VP8L";
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u24(b: &[u8]) -> u32 {
+    u32::from(b[0]) | (u32::from(b[1]) << 8) | (u32::from(b[2]) << 16)
+}
+
+fn write_u24(out: &mut Vec<u8>, v: u32) {
+    out.push((v & 0xff) as u8);
+    out.push(((v >> 8) & 0xff) as u8);
+    out.push(((v >> 16) & 0xff) as u8);
+}
+
+/// Find the data of the top-level subchunk with id `id`, if any.
+fn find_data(subchunks: &[RiffChunk], id: [u8; 4]) -> Option<Bytes> {
+    subchunks
+        .iter()
+        .find(|c| c.id() == id)
+        .and_then(|c| c.content().data())
+}
+
+/// The decoded `VP8X` chunk: the extended WebP feature flags and canvas
+/// size of a `RIFF....WEBP` container.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vp8x {
+    pub icc: bool,
+    pub alpha: bool,
+    pub exif: bool,
+    pub xmp: bool,
+    pub anim: bool,
+    canvas_width_minus_one: u32,
+    canvas_height_minus_one: u32,
+}
+
+impl Vp8x {
+    /// Construct a `Vp8x` from a 1-based `canvas_width`/`canvas_height`
+    /// (the actual canvas dimensions, each between 1 and 2^24 inclusive, so
+    /// that the encoded value, the dimension minus one, fits in 24 bits).
+    pub fn new(
+        icc: bool,
+        alpha: bool,
+        exif: bool,
+        xmp: bool,
+        anim: bool,
+        canvas_width: u32,
+        canvas_height: u32,
+    ) -> Result<Vp8x> {
+        if canvas_width == 0 || canvas_height == 0 {
+            return Err(invalid_data("VP8X canvas dimensions must be non-zero").into());
+        }
+
+        let canvas_width_minus_one = canvas_width - 1;
+        let canvas_height_minus_one = canvas_height - 1;
+
+        if canvas_width_minus_one > 0x00ff_ffff || canvas_height_minus_one > 0x00ff_ffff {
+            return Err(invalid_data("VP8X canvas dimensions must fit in 24 bits").into());
+        }
+
+        Ok(Vp8x {
+            icc,
+            alpha,
+            exif,
+            xmp,
+            anim,
+            canvas_width_minus_one,
+            canvas_height_minus_one,
+        })
+    }
+
+    /// Parse a `Vp8x` out of the raw contents of a `VP8X` chunk.
+    pub fn from_bytes(data: &[u8]) -> Result<Vp8x> {
+        if data.len() < 10 {
+            return Err(invalid_data("VP8X chunk is too short").into());
+        }
+
+        let flags = data[0];
+
+        Ok(Vp8x {
+            icc: flags & 0b0010_0000 != 0,
+            alpha: flags & 0b0001_0000 != 0,
+            exif: flags & 0b0000_1000 != 0,
+            xmp: flags & 0b0000_0100 != 0,
+            anim: flags & 0b0000_0010 != 0,
+            canvas_width_minus_one: read_u24(&data[4..7]),
+            canvas_height_minus_one: read_u24(&data[7..10]),
+        })
+    }
+
+    /// Parse the `VP8X` chunk among `subchunks`, if present.
+    pub fn find(subchunks: &[RiffChunk]) -> Result<Option<Vp8x>> {
+        find_data(subchunks, VP8X_ID)
+            .map(|data| Vp8x::from_bytes(&data))
+            .transpose()
+    }
+
+    /// The actual canvas width (the encoded value plus one).
+    pub fn canvas_width(&self) -> u32 {
+        self.canvas_width_minus_one + 1
+    }
+
+    /// The actual canvas height (the encoded value plus one).
+    pub fn canvas_height(&self) -> u32 {
+        self.canvas_height_minus_one + 1
+    }
+
+    /// Encode this `Vp8x` into the raw contents of a `VP8X` chunk.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut flags = 0u8;
+        if self.icc {
+            flags |= 0b0010_0000;
+        }
+        if self.alpha {
+            flags |= 0b0001_0000;
+        }
+        if self.exif {
+            flags |= 0b0000_1000;
+        }
+        if self.xmp {
+            flags |= 0b0000_0100;
+        }
+        if self.anim {
+            flags |= 0b0000_0010;
+        }
+
+        let mut out = Vec::with_capacity(10);
+        out.push(flags);
+        out.extend([0u8; 3]);
+        write_u24(&mut out, self.canvas_width_minus_one);
+        write_u24(&mut out, self.canvas_height_minus_one);
+        out
+    }
+
+    /// Re-encode this `Vp8x` as a `VP8X` `RiffChunk`.
+    pub fn to_chunk(&self) -> RiffChunk {
+        RiffChunk::new(VP8X_ID, RiffContent::Data(Bytes::from(self.to_bytes())))
+    }
+}
+
+/// The decoded `ANIM` chunk: the animation background color and loop
+/// count of an animated `RIFF....WEBP` container.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Anim {
+    pub background_color: u32,
+    pub loop_count: u16,
+}
+
+impl Anim {
+    /// Parse an `Anim` out of the raw contents of an `ANIM` chunk.
+    pub fn from_bytes(data: &[u8]) -> Result<Anim> {
+        if data.len() < 6 {
+            return Err(invalid_data("ANIM chunk is too short").into());
+        }
+
+        Ok(Anim {
+            background_color: u32::from_le_bytes(data[0..4].try_into().unwrap()),
+            loop_count: u16::from_le_bytes(data[4..6].try_into().unwrap()),
+        })
+    }
+
+    /// Parse the `ANIM` chunk among `subchunks`, if present.
+    pub fn find(subchunks: &[RiffChunk]) -> Result<Option<Anim>> {
+        find_data(subchunks, ANIM_ID)
+            .map(|data| Anim::from_bytes(&data))
+            .transpose()
+    }
+
+    /// Encode this `Anim` into the raw contents of an `ANIM` chunk.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(6);
+        out.extend(self.background_color.to_le_bytes());
+        out.extend(self.loop_count.to_le_bytes());
+        out
+    }
+
+    /// Re-encode this `Anim` as an `ANIM` `RiffChunk`.
+    pub fn to_chunk(&self) -> RiffChunk {
+        RiffChunk::new(ANIM_ID, RiffContent::Data(Bytes::from(self.to_bytes())))
+    }
+}
+
+/// The blending method of an `ANMF` frame, as encoded in bit 1 of its flags
+/// byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendingMethod {
+    AlphaBlend,
+    DoNotBlend,
+}
+
+/// The disposal method of an `ANMF` frame, as encoded in bit 0 of its flags
+/// byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisposalMethod {
+    DoNotDispose,
+    DisposeToBackground,
+}
+
+/// A decoded `ANMF` chunk: one frame of an animated `RIFF....WEBP`
+/// container, with its offset, size, duration, blend/dispose flags and
+/// embedded bitstream subchunks (an optional `ALPH` followed by a `VP8` or
+/// `VP8L` chunk).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Anmf {
+    pub frame_x: u32,
+    pub frame_y: u32,
+    frame_width_minus_one: u32,
+    frame_height_minus_one: u32,
+    frame_duration: u32,
+    pub flags: u8,
+    subchunks: Vec<RiffChunk>,
+}
+
+impl Anmf {
+    /// Parse an `Anmf` out of the raw contents of an `ANMF` chunk.
+    pub fn from_bytes(data: &[u8]) -> Result<Anmf> {
+        if data.len() < 16 {
+            return Err(invalid_data("ANMF chunk is too short").into());
+        }
+
+        let mut subchunk_data = Bytes::copy_from_slice(&data[16..]);
+        let mut subchunks = Vec::new();
+        while !subchunk_data.is_empty() {
+            subchunks.push(RiffChunk::from_bytes_impl(&mut subchunk_data, false)?);
+        }
+
+        Ok(Anmf {
+            frame_x: read_u24(&data[0..3]),
+            frame_y: read_u24(&data[3..6]),
+            frame_width_minus_one: read_u24(&data[6..9]),
+            frame_height_minus_one: read_u24(&data[9..12]),
+            frame_duration: read_u24(&data[12..15]),
+            flags: data[15],
+            subchunks,
+        })
+    }
+
+    /// Parse every `ANMF` chunk among `subchunks`, in order.
+    pub fn find_all(subchunks: &[RiffChunk]) -> Result<Vec<Anmf>> {
+        subchunks
+            .iter()
+            .filter(|c| c.id() == ANMF_ID)
+            .map(|c| {
+                c.content()
+                    .data()
+                    .ok_or_else(|| invalid_data("ANMF chunk is not a Data chunk").into())
+                    .and_then(|data| Anmf::from_bytes(&data))
+            })
+            .collect()
+    }
+
+    /// The actual frame width (the encoded value plus one).
+    pub fn frame_width(&self) -> u32 {
+        self.frame_width_minus_one + 1
+    }
+
+    /// The actual frame height (the encoded value plus one).
+    pub fn frame_height(&self) -> u32 {
+        self.frame_height_minus_one + 1
+    }
+
+    /// The frame duration, in milliseconds.
+    pub fn duration(&self) -> u32 {
+        self.frame_duration
+    }
+
+    /// The frame's blending method (flags bit 1).
+    pub fn blending_method(&self) -> BlendingMethod {
+        if self.flags & 0b10 != 0 {
+            BlendingMethod::DoNotBlend
+        } else {
+            BlendingMethod::AlphaBlend
+        }
+    }
+
+    /// The frame's disposal method (flags bit 0).
+    pub fn disposal_method(&self) -> DisposalMethod {
+        if self.flags & 0b01 != 0 {
+            DisposalMethod::DisposeToBackground
+        } else {
+            DisposalMethod::DoNotDispose
+        }
+    }
+
+    /// The frame's embedded `ALPH` chunk, if any.
+    pub fn alpha_chunk(&self) -> Option<&RiffChunk> {
+        self.subchunks.iter().find(|c| c.id() == ALPH_ID)
+    }
+
+    /// The frame's embedded bitstream chunk (`VP8` or `VP8L`).
+    pub fn bitstream_chunk(&self) -> Option<&RiffChunk> {
+        self.subchunks
+            .iter()
+            .find(|c| c.id() == VP8_ID || c.id() == VP8L_ID)
+    }
+
+    /// Encode this `Anmf` into the raw contents of an `ANMF` chunk.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(16);
+        write_u24(&mut out, self.frame_x);
+        write_u24(&mut out, self.frame_y);
+        write_u24(&mut out, self.frame_width_minus_one);
+        write_u24(&mut out, self.frame_height_minus_one);
+        write_u24(&mut out, self.frame_duration);
+        out.push(self.flags);
+
+        for subchunk in &self.subchunks {
+            subchunk.write_to(&mut out)?;
+        }
+
+        Ok(out)
+    }
+
+    /// Re-encode this `Anmf` as an `ANMF` `RiffChunk`.
+    pub fn to_chunk(&self) -> Result<RiffChunk> {
+        Ok(RiffChunk::new(
+            ANMF_ID,
+            RiffContent::Data(Bytes::from(self.to_bytes()?)),
+        ))
+    }
+}